@@ -2,11 +2,18 @@ extern crate google_youtube3 as youtube3;
 use google_youtube3::YouTube;
 use std::path::Path;
 
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fs;
+use std::io::Read;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+use std::time::{SystemTime, UNIX_EPOCH};
 use yup_oauth2::ApplicationSecret;
 use yup_oauth2::Authenticator;
 use yup_oauth2::DefaultAuthenticatorDelegate;
-use yup_oauth2::MemoryStorage;
+use yup_oauth2::DiskTokenStorage;
 
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
@@ -18,6 +25,11 @@ use scraper::{Html, Selector};
 // Some API calls need to be called multiple times to get all the items.
 const MAX_RESULTS: u32 = 40;
 
+// Token cache shared by every `YoutubeClient` a run creates (including the per-worker-thread
+// clients spawned by SavePlaylistsToJson's `--parallel`), so OAuth consent only has to happen
+// once per working directory (the path is relative) instead of once per client.
+const TOKEN_CACHE_FILE: &str = "youtube-list-token-cache.json";
+
 #[derive(Debug, PartialEq, StructOpt)]
 #[structopt(about = "Manages youtube playlists")]
 enum Subcommands {
@@ -26,6 +38,15 @@ enum Subcommands {
         /// Output file, stdout if not present
         #[structopt(parse(from_os_str))]
         output_file: Option<PathBuf>,
+        /// Number of playlists to fetch items for concurrently
+        #[structopt(long, default_value = "8")]
+        parallel: usize,
+        /// Skip re-fetching a playlist's items if the cache entry is younger than this many seconds
+        #[structopt(long, default_value = "3600")]
+        cache_ttl: u64,
+        /// Disk cache file, "youtube-list-cache.json" if not present
+        #[structopt(long, parse(from_os_str))]
+        cache_file: Option<PathBuf>,
     },
     // Parses an html that was saved from the Watch Later playlist page and saves the available information to a json file.
     SaveWatchLaterHtmlToJson {
@@ -34,15 +55,72 @@ enum Subcommands {
         #[structopt(parse(from_os_str))]
         output_file: Option<PathBuf>,
     },
+    // Scrapes a public playlist's page directly over HTTP and saves it to a json file, without
+    // needing an application secret or touching the YouTube Data API quota.
+    ScrapePlaylistToJson {
+        /// Id of the public playlist to scrape
+        playlist_id: String,
+        #[structopt(parse(from_os_str))]
+        output_file: Option<PathBuf>,
+    },
+    // Saves all playlists information as an RSS 2.0 feed instead of json, so playlists can be
+    // subscribed to in a podcast/feed reader.
+    SavePlaylistsToRss {
+        /// Json file produced by SavePlaylistsToJson or SaveWatchLaterHtmlToJson; fetched live from the API when absent
+        #[structopt(parse(from_os_str))]
+        input_file: Option<PathBuf>,
+        /// Output file, "youtube-output.rss" if not present
+        #[structopt(parse(from_os_str))]
+        output_file: Option<PathBuf>,
+    },
+    // Polls a playlist on an interval and prints the items that were added or removed since
+    // the last poll, persisting the last seen items to a state file between runs.
+    WatchPlaylist {
+        /// Id of the playlist to poll
+        playlist_id: String,
+        /// Seconds to sleep between polls
+        poll_interval_secs: u64,
+        /// File used to persist the last seen items across polls
+        #[structopt(parse(from_os_str))]
+        state_file: PathBuf,
+    },
+    // Downloads every item of a previously saved playlist (json produced by SavePlaylistsToJson
+    // or SaveWatchLaterHtmlToJson) via yt-dlp, and enriches the saved items with the duration,
+    // uploader and resolution reported by yt-dlp.
+    DownloadPlaylist {
+        /// Json file produced by SavePlaylistsToJson or SaveWatchLaterHtmlToJson
+        #[structopt(parse(from_os_str))]
+        input_file: PathBuf,
+        /// Directory where the downloaded videos (or audio) will be written
+        #[structopt(parse(from_os_str))]
+        output_dir: PathBuf,
+        /// Only download the audio track instead of the full video
+        #[structopt(long)]
+        audio_only: bool,
+    },
+    // Recreates playlists on the authenticated account from a json file previously produced by
+    // SavePlaylistsToJson, including all of their items, in order.
+    RestorePlaylistsFromJson {
+        /// Json file produced by SavePlaylistsToJson
+        #[structopt(parse(from_os_str))]
+        input_file: PathBuf,
+        /// Log the playlists/items that would be created instead of calling the API
+        #[structopt(long)]
+        dry_run: bool,
+    },
 }
 
 // Only serves a convenience wrapper for the hub type.
+//
+// Backed by DiskTokenStorage rather than MemoryStorage so that a cached token survives across
+// the several `YoutubeClient`s a single run can create (one per SavePlaylistsToJson worker
+// thread): the OAuth consent only has to happen once per token cache file, not once per thread.
 struct YoutubeClient {
     hub: youtube3::YouTube<
         hyper::Client,
         yup_oauth2::Authenticator<
             yup_oauth2::DefaultAuthenticatorDelegate,
-            yup_oauth2::MemoryStorage,
+            yup_oauth2::DiskTokenStorage,
             hyper::Client,
         >,
     >,
@@ -60,13 +138,19 @@ struct Playlist {
     items: Vec<PlaylistItem>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 struct PlaylistItem {
     title: String,
     link: String,
     published_at: String,
     position_in_playlist: u32,
     description: String,
+    #[serde(default)]
+    duration: Option<f64>,
+    #[serde(default)]
+    uploader: Option<String>,
+    #[serde(default)]
+    resolution: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -75,6 +159,12 @@ struct SimplePlaylistItem {
     channel_name: String,
     link: String,
     id: String,
+    #[serde(default)]
+    duration: Option<f64>,
+    #[serde(default)]
+    uploader: Option<String>,
+    #[serde(default)]
+    resolution: Option<String>,
 }
 
 #[derive(Debug, PartialEq, StructOpt)]
@@ -110,6 +200,9 @@ impl PlaylistItem {
             published_at: String::new(),
             position_in_playlist: 0u32,
             description: String::new(),
+            duration: None,
+            uploader: None,
+            resolution: None,
         }
     }
 }
@@ -168,6 +261,178 @@ fn parse_playlist_items(client: &YoutubeClient, playlist_id: &str) -> Vec<youtub
     return playlist_items;
 }
 
+// Fetches every playlist owned by the authenticated account, together with its items,
+// already converted to the on-disk `Playlist` representation.
+fn fetch_all_playlists(client: &YoutubeClient) -> Vec<Playlist> {
+    let mut output_playlists = Vec::<Playlist>::new();
+    let playlists = request_playlists(client);
+
+    for p in playlists {
+        let mut playlist = parse_playlist(&p);
+
+        match p.id {
+            Some(ref id) => {
+                let items = parse_playlist_items(client, &id);
+
+                let mut playlist_items = Vec::<PlaylistItem>::new();
+                for item in items {
+                    let playlist_item = parse_playlist_item(&item);
+                    playlist_items.push(playlist_item);
+                }
+
+                playlist.items = playlist_items;
+            }
+            None => {
+                eprintln!("Error: Failed to get playlist id from playlist: {:?}", p);
+                continue;
+            }
+        }
+
+        output_playlists.push(playlist);
+    }
+
+    return output_playlists;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct PlaylistItemsCacheEntry {
+    fetched_at: u64,
+    items: Vec<PlaylistItem>,
+}
+
+type PlaylistItemsCache = HashMap<String, PlaylistItemsCacheEntry>;
+
+fn load_playlist_items_cache(cache_file: &Path) -> PlaylistItemsCache {
+    match fs::read_to_string(cache_file) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_default(),
+        Err(_) => PlaylistItemsCache::new(),
+    }
+}
+
+fn save_playlist_items_cache(cache_file: &Path, cache: &PlaylistItemsCache) {
+    if let Ok(text) = serde_json::to_string(cache) {
+        fs::write(cache_file, &text).expect("Unable to write cache file");
+    }
+}
+
+fn unix_timestamp_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System clock is before the Unix epoch")
+        .as_secs()
+}
+
+// Splits `items` into `worker_count` roughly even chunks, round-robin, so each worker thread
+// gets a similar share of the work.
+fn partition_round_robin<T>(items: Vec<T>, worker_count: usize) -> Vec<Vec<T>> {
+    let worker_count = worker_count.max(1);
+    let mut chunks: Vec<Vec<T>> = (0..worker_count).map(|_| Vec::new()).collect();
+
+    for (i, item) in items.into_iter().enumerate() {
+        chunks[i % worker_count].push(item);
+    }
+
+    return chunks;
+}
+
+// Same as `fetch_all_playlists`, but fetches up to `parallel` playlists' items concurrently
+// and skips the network call entirely for playlists with a fresh enough cache entry.
+fn fetch_all_playlists_cached(
+    application_secret_file: Option<PathBuf>,
+    parallel: usize,
+    cache_ttl_secs: u64,
+    cache: PlaylistItemsCache,
+) -> (Vec<Playlist>, PlaylistItemsCache) {
+    // Authenticate once up front so the token lands in TOKEN_CACHE_FILE. Every worker thread
+    // below still builds its own YoutubeClient (so its network calls never contend with any
+    // other thread's), but build_client's DiskTokenStorage picks up that cached token instead
+    // of running the interactive OAuth flow again.
+    let playlists = {
+        let client = build_client(application_secret_file.clone());
+        request_playlists(&client)
+    };
+
+    let now = unix_timestamp_now();
+    // Each playlist carries its index so the output can be put back in the API's order.
+    let chunks = partition_round_robin(playlists.into_iter().enumerate().collect(), parallel);
+
+    let handles = chunks
+        .into_iter()
+        .map(|chunk| {
+            let application_secret_file = application_secret_file.clone();
+            let cache = cache.clone();
+
+            thread::spawn(move || {
+                let client = build_client(application_secret_file);
+                let mut output_playlists = Vec::new();
+                let mut fresh_entries = Vec::new();
+
+                for (index, p) in chunk {
+                    let mut playlist = parse_playlist(&p);
+
+                    let playlist_id = match p.id {
+                        Some(ref id) => id.clone(),
+                        None => {
+                            eprintln!("Error: Failed to get playlist id from playlist: {:?}", p);
+                            continue;
+                        }
+                    };
+
+                    let cached_items = cache.get(&playlist_id).and_then(|entry| {
+                        if now.saturating_sub(entry.fetched_at) < cache_ttl_secs {
+                            Some(entry.items.clone())
+                        } else {
+                            None
+                        }
+                    });
+
+                    playlist.items = match cached_items {
+                        Some(items) => items,
+                        None => {
+                            let items = parse_playlist_items(&client, &playlist_id)
+                                .iter()
+                                .map(parse_playlist_item)
+                                .collect::<Vec<PlaylistItem>>();
+
+                            fresh_entries.push((
+                                playlist_id.clone(),
+                                PlaylistItemsCacheEntry {
+                                    fetched_at: now,
+                                    items: items.clone(),
+                                },
+                            ));
+
+                            items
+                        }
+                    };
+
+                    playlist.id = playlist_id;
+                    output_playlists.push((index, playlist));
+                }
+
+                (output_playlists, fresh_entries)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut output_playlists = Vec::new();
+    let mut cache = cache;
+
+    for handle in handles {
+        let (playlists, fresh_entries) = handle.join().expect("Playlist fetch worker panicked");
+        output_playlists.extend(playlists);
+
+        for (playlist_id, entry) in fresh_entries {
+            cache.insert(playlist_id, entry);
+        }
+    }
+
+    output_playlists.sort_by_key(|(index, _)| *index);
+    let output_playlists = output_playlists.into_iter().map(|(_, playlist)| playlist).collect();
+
+    return (output_playlists, cache);
+}
+
 fn get_text(option: &Option<String>, default: &str) -> String {
     match option {
         Some(text) => text.clone(),
@@ -248,14 +513,434 @@ fn split_video_id(link: &str) -> String {
     return parts[0].to_string();
 }
 
-fn main() {
-    let opt = Opt::from_args();
-    println!("Arguments: {:?}", opt);
+// Recreates a single saved playlist (and its items, in order) on the authenticated account.
+// Returns the number of items that failed to be added, or an error if the playlist itself
+// couldn't be created.
+fn restore_playlist(client: &YoutubeClient, playlist: &Playlist) -> Result<usize, String> {
+    let new_playlist = youtube3::Playlist {
+        snippet: Some(youtube3::PlaylistSnippet {
+            title: Some(playlist.title.clone()),
+            description: Some(playlist.description.clone()),
+            ..Default::default()
+        }),
+        status: Some(youtube3::PlaylistStatus {
+            privacy_status: Some(playlist.status.clone()),
+        }),
+        ..Default::default()
+    };
+
+    let (_resp, created) = client
+        .hub
+        .playlists()
+        .insert(new_playlist, "snippet,status")
+        .doit()
+        .map_err(|e| format!("{:?}", e))?;
+
+    let new_playlist_id = created
+        .id
+        .ok_or_else(|| "API did not return a playlist id".to_string())?;
+
+    let mut items = playlist.items.iter().collect::<Vec<&PlaylistItem>>();
+    items.sort_by_key(|item| item.position_in_playlist);
+
+    let mut failures = 0usize;
+    for (i, item) in items.iter().enumerate() {
+        let video_id = split_video_id(&item.link);
+
+        let new_item = youtube3::PlaylistItem {
+            snippet: Some(youtube3::PlaylistItemSnippet {
+                playlist_id: Some(new_playlist_id.clone()),
+                position: Some(item.position_in_playlist),
+                resource_id: Some(youtube3::ResourceId {
+                    kind: Some("youtube#video".to_string()),
+                    video_id: Some(video_id.clone()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        if let Err(e) = client.hub.playlist_items().insert(new_item, "snippet").doit() {
+            eprintln!(
+                "Failed to add video {} to playlist {:?}: {:?}",
+                video_id, playlist.title, e
+            );
+            failures += 1;
+        }
+
+        if (i + 1) % MAX_RESULTS as usize == 0 {
+            println!("...added {} items to {:?}", i + 1, playlist.title);
+        }
+    }
+
+    return Ok(failures);
+}
+
+// Reads the items persisted by a previous WatchPlaylist poll, or an empty list if the state
+// file doesn't exist yet (the first poll).
+fn load_watch_state(state_file: &Path) -> Vec<PlaylistItem> {
+    match fs::read_to_string(state_file) {
+        Ok(contents) => serde_json::from_str::<Vec<PlaylistItem>>(&contents).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn save_watch_state(state_file: &Path, items: &[PlaylistItem]) {
+    if let Ok(text) = serde_json::to_string(items) {
+        fs::write(state_file, &text).expect("Unable to write state file");
+    }
+}
+
+// Prints the items present in `current` but not in `previous`, and vice versa, keyed by video id.
+fn print_watch_diff(previous: &[PlaylistItem], current: &[PlaylistItem]) {
+    let previous_ids: HashSet<String> = previous.iter().map(|item| split_video_id(&item.link)).collect();
+    let current_ids: HashSet<String> = current.iter().map(|item| split_video_id(&item.link)).collect();
+
+    for item in current {
+        if !previous_ids.contains(&split_video_id(&item.link)) {
+            println!("+ {} ({})", item.title, item.link);
+        }
+    }
+
+    for item in previous {
+        if !current_ids.contains(&split_video_id(&item.link)) {
+            println!("- {} ({})", item.title, item.link);
+        }
+    }
+}
+
+// Scrapes the items out of a playlist page's rendered html. Used both for a locally saved
+// Watch Later page and for a publicly scraped playlist, since YouTube renders both with the
+// same ytd-playlist-video-renderer markup.
+fn scrape_simple_playlist_items(contents: &str) -> Vec<SimplePlaylistItem> {
+    let mut playlist_items = Vec::<SimplePlaylistItem>::new();
+
+    let html = Html::parse_fragment(contents);
+
+    let item_selector = Selector::parse("#content").unwrap();
+    let video_title = Selector::parse("#video-title").unwrap();
+    let channel_title = Selector::parse("#text").unwrap();
+    let video_link = Selector::parse("#content > a").unwrap();
+
+    let items = html.select(&item_selector);
+    for item in items {
+        let mut title = item.select(&video_title);
+        let mut channel = item.select(&channel_title);
+        let mut video_link = item.select(&video_link);
+
+        let item_title = if let Some(a) = title.next() {
+            a.text().collect::<String>().trim().to_string()
+        } else {
+            eprintln!("No title?");
+            String::new()
+        };
+
+        let item_channel = if let Some(a) = channel.next() {
+            a.text().collect::<String>().trim().to_string()
+        } else {
+            eprintln!("No channel title?");
+            String::new()
+        };
+
+        let item_link = if let Some(a) = video_link.next() {
+            let item_link = a.value().attr("href").unwrap_or("").to_string();
+            let video_id = split_video_id(&item_link);
+            (item_link, video_id)
+        } else {
+            eprintln!("No video_link?");
+            (String::new(), String::new())
+        };
+
+        let item = SimplePlaylistItem {
+            title: item_title,
+            channel_name: item_channel,
+            id: item_link.1,
+            link: item_link.0,
+            duration: None,
+            uploader: None,
+            resolution: None,
+        };
+
+        playlist_items.push(item);
+    }
+
+    return playlist_items
+        .into_iter()
+        .filter(|x| !x.id.is_empty())
+        .collect();
+}
+
+// Converts the flat items produced by scraping (or by SaveWatchLaterHtmlToJson) into the same
+// `Playlist` shape used by the API-backed subcommands, so that output can be fed into
+// SavePlaylistsToRss/RestorePlaylistsFromJson too. There's no snippet/status to scrape, so those
+// fields are left empty.
+fn simple_items_to_playlist(playlist_id: &str, items: Vec<SimplePlaylistItem>) -> Playlist {
+    let mut playlist = Playlist::new();
+    playlist.id = playlist_id.to_string();
+    playlist.items = items
+        .into_iter()
+        .enumerate()
+        .map(|(position, item)| PlaylistItem {
+            title: item.title,
+            link: item.link,
+            published_at: String::new(),
+            position_in_playlist: position as u32,
+            description: String::new(),
+            duration: item.duration,
+            uploader: Some(item.channel_name),
+            resolution: item.resolution,
+        })
+        .collect();
+
+    return playlist;
+}
+
+// Scrapes a public playlist's page over plain HTTP, without touching the YouTube Data API or
+// requiring an application secret.
+fn scrape_playlist(playlist_id: &str) -> Playlist {
+    let url = format!("https://www.youtube.com/playlist?list={}", playlist_id);
+    let contents = fetch_url(&url);
+    let items = scrape_simple_playlist_items(&contents);
+
+    return simple_items_to_playlist(playlist_id, items);
+}
+
+// Fetches a public playlist's page over plain HTTP, without touching the YouTube Data API
+// or requiring an application secret.
+fn fetch_url(url: &str) -> String {
+    let client = hyper::Client::with_connector(hyper::net::HttpsConnector::new(
+        hyper_rustls::TlsClient::new(),
+    ));
+
+    let mut response = client.get(url).send().expect("Failed to fetch url");
+
+    let mut body = String::new();
+    response
+        .read_to_string(&mut body)
+        .expect("Failed to read response body");
+
+    return body;
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn month_name(month: u32) -> &'static str {
+    const NAMES: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    NAMES[(month as usize - 1).min(11)]
+}
+
+// Zeller's congruence, used instead of pulling in a date/time crate for a single field.
+fn weekday_name(year: i32, month: u32, day: u32) -> &'static str {
+    let (y, m) = if month < 3 {
+        (year - 1, month + 12)
+    } else {
+        (year, month)
+    };
+    let k = y % 100;
+    let j = y / 100;
+    let h = (day as i32 + (13 * (m as i32 + 1)) / 5 + k + k / 4 + j / 4 + 5 * j) % 7;
+
+    const NAMES: [&str; 7] = [
+        "Sat", "Sun", "Mon", "Tue", "Wed", "Thu", "Fri",
+    ];
+    NAMES[h as usize]
+}
+
+// Reformats the API's "2020-01-02T15:04:05Z" timestamps into RFC-2822, as required by
+// <pubDate> in RSS 2.0. Returns an empty string if published_at couldn't be parsed.
+fn to_rfc2822(published_at: &str) -> String {
+    let trimmed = published_at.trim_end_matches('Z');
+    let mut date_and_time = trimmed.splitn(2, 'T');
+
+    let date_part = date_and_time.next().unwrap_or("");
+    let time_part = date_and_time.next().unwrap_or("00:00:00");
+
+    let mut date_fields = date_part.split('-');
+    let year = date_fields.next().and_then(|s| s.parse::<i32>().ok());
+    let month = date_fields.next().and_then(|s| s.parse::<u32>().ok());
+    let day = date_fields.next().and_then(|s| s.parse::<u32>().ok());
+
+    let mut time_fields = time_part.split(':');
+    let hour = time_fields.next().unwrap_or("00").parse::<u32>().unwrap_or(0);
+    let minute = time_fields.next().unwrap_or("00").parse::<u32>().unwrap_or(0);
+    let second = time_fields.next().unwrap_or("00").parse::<u32>().unwrap_or(0);
+
+    match (year, month, day) {
+        (Some(year), Some(month), Some(day)) => format!(
+            "{}, {:02} {} {} {:02}:{:02}:{:02} +0000",
+            weekday_name(year, month, day),
+            day,
+            month_name(month),
+            year,
+            hour,
+            minute,
+            second
+        ),
+        _ => String::new(),
+    }
+}
+
+fn playlist_item_to_rss(item: &PlaylistItem) -> String {
+    let video_id = split_video_id(&item.link);
+
+    format!(
+        "    <item>\n      <title>{}</title>\n      <link>{}</link>\n      <guid>{}</guid>\n      <pubDate>{}</pubDate>\n      <enclosure url=\"{}\" />\n    </item>\n",
+        escape_xml(&item.title),
+        escape_xml(&item.link),
+        escape_xml(&video_id),
+        to_rfc2822(&item.published_at),
+        escape_xml(&item.link)
+    )
+}
 
+fn playlist_to_rss_channel(playlist: &Playlist) -> String {
+    let mut channel = String::new();
+
+    channel.push_str("  <channel>\n");
+    channel.push_str(&format!("    <title>{}</title>\n", escape_xml(&playlist.title)));
+    channel.push_str(&format!(
+        "    <link>https://www.youtube.com/playlist?list={}</link>\n",
+        escape_xml(&playlist.id)
+    ));
+    channel.push_str(&format!(
+        "    <description>{}</description>\n",
+        escape_xml(&playlist.description)
+    ));
+    channel.push_str(&format!(
+        "    <managingEditor>{}</managingEditor>\n",
+        escape_xml(&playlist.channel_title)
+    ));
+    channel.push_str(&format!(
+        "    <pubDate>{}</pubDate>\n",
+        to_rfc2822(&playlist.published_at)
+    ));
+
+    for item in &playlist.items {
+        channel.push_str(&playlist_item_to_rss(item));
+    }
+
+    channel.push_str("  </channel>\n");
+
+    return channel;
+}
+
+fn playlists_to_rss(playlists: &[Playlist]) -> String {
+    let mut rss = String::new();
+
+    rss.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    rss.push_str("<rss version=\"2.0\">\n");
+
+    for playlist in playlists {
+        rss.push_str(&playlist_to_rss_channel(playlist));
+    }
+
+    rss.push_str("</rss>\n");
+
+    return rss;
+}
+
+// Replaces characters that are awkward in file names so the yt-dlp output template stays
+// a single path component.
+fn sanitize_filename(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+fn build_output_template(output_dir: &Path, position: u32, title: &str) -> String {
+    output_dir
+        .join(format!("{:04}-{}.%(ext)s", position, sanitize_filename(title)))
+        .to_string_lossy()
+        .to_string()
+}
+
+// Shells out to yt-dlp for a single video, asking it to dump the metadata it found as json
+// after the download completes. Returns None if yt-dlp isn't installed or fails for that link.
+fn download_with_ytdlp(link: &str, output_template: &str, audio_only: bool) -> Option<serde_json::Value> {
+    let mut command = Command::new("yt-dlp");
+    command
+        .arg("-o")
+        .arg(output_template)
+        .arg("--dump-json")
+        .arg("--no-simulate");
+
+    if audio_only {
+        command.arg("-x");
+    }
+
+    command.arg(link);
+
+    match command.output() {
+        Ok(output) if output.status.success() => {
+            serde_json::from_slice::<serde_json::Value>(&output.stdout).ok()
+        }
+        Ok(output) => {
+            eprintln!(
+                "yt-dlp failed for {}: {}",
+                link,
+                String::from_utf8_lossy(&output.stderr)
+            );
+            None
+        }
+        Err(e) => {
+            eprintln!("Failed to invoke yt-dlp for {}: {}", link, e);
+            None
+        }
+    }
+}
+
+fn download_playlist_item(item: &mut PlaylistItem, output_dir: &Path, audio_only: bool) {
+    let output_template = build_output_template(output_dir, item.position_in_playlist, &item.title);
+
+    if let Some(info) = download_with_ytdlp(&item.link, &output_template, audio_only) {
+        item.duration = info.get("duration").and_then(|v| v.as_f64());
+        item.uploader = info
+            .get("uploader")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+        item.resolution = info
+            .get("resolution")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+    }
+}
+
+fn download_simple_playlist_item(item: &mut SimplePlaylistItem, position: u32, output_dir: &Path, audio_only: bool) {
+    let output_template = build_output_template(output_dir, position, &item.title);
+
+    if let Some(info) = download_with_ytdlp(&item.link, &output_template, audio_only) {
+        item.duration = info.get("duration").and_then(|v| v.as_f64());
+        item.uploader = info
+            .get("uploader")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+        item.resolution = info
+            .get("resolution")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+    }
+}
+
+// Builds the authenticated API client, running the OAuth dance if needed. Only the
+// subcommands that actually talk to the YouTube Data API call this, since it requires an
+// application secret file that scraping-only workflows shouldn't need.
+fn build_client(application_secret_file: Option<PathBuf>) -> YoutubeClient {
     // You will have to create an application in console.developers.google.com to use this.
     // In particular, once you're there, search for YouTube Data API v3 and go to credentials.
     // You can download this file by creating a new OAuth client ID credential.
-    let application_secret_file = if let Some(path) = opt.application_secret_file {
+    let application_secret_file = if let Some(path) = application_secret_file {
         path
     } else {
         std::path::Path::new("../client_secret_console_developers_google_com.json").to_path_buf()
@@ -264,13 +949,16 @@ fn main() {
     let secret: ApplicationSecret = yup_oauth2::read_application_secret(&application_secret_file)
         .expect("Secrets file not found");
 
+    let token_storage = DiskTokenStorage::new(&TOKEN_CACHE_FILE.to_string())
+        .expect("Unable to open token cache file");
+
     let auth = Authenticator::new(
         &secret,
         DefaultAuthenticatorDelegate,
         hyper::Client::with_connector(hyper::net::HttpsConnector::new(
             hyper_rustls::TlsClient::new(),
         )),
-        <MemoryStorage as Default>::default(),
+        token_storage,
         None,
     );
 
@@ -281,36 +969,29 @@ fn main() {
         auth,
     );
 
-    let client = YoutubeClient { hub: hub };
-
-    match opt.sub {
-        Subcommands::SavePlaylistsToJson { output_file } => {
-            let mut output_playlists = Vec::<Playlist>::new();
-            let playlists = request_playlists(&client);
+    return YoutubeClient { hub: hub };
+}
 
-            for p in playlists {
-                let mut playlist = parse_playlist(&p);
+fn main() {
+    let opt = Opt::from_args();
+    println!("Arguments: {:?}", opt);
 
-                match p.id {
-                    Some(ref id) => {
-                        let items = parse_playlist_items(&client, &id);
+    let application_secret_file = opt.application_secret_file;
 
-                        let mut playlist_items = Vec::<PlaylistItem>::new();
-                        for item in items {
-                            let playlist_item = parse_playlist_item(&item);
-                            playlist_items.push(playlist_item);
-                        }
+    match opt.sub {
+        Subcommands::SavePlaylistsToJson {
+            output_file,
+            parallel,
+            cache_ttl,
+            cache_file,
+        } => {
+            let cache_file = cache_file.unwrap_or_else(|| Path::new("youtube-list-cache.json").to_path_buf());
+            let cache = load_playlist_items_cache(&cache_file);
 
-                        playlist.items = playlist_items;
-                    }
-                    None => {
-                        eprintln!("Error: Failed to get playlist id from playlist: {:?}", p);
-                        continue;
-                    }
-                }
+            let (output_playlists, cache) =
+                fetch_all_playlists_cached(application_secret_file, parallel, cache_ttl, cache);
 
-                output_playlists.push(playlist);
-            }
+            save_playlist_items_cache(&cache_file, &cache);
 
             let path = if let Some(path) = output_file {
                 path
@@ -331,63 +1012,7 @@ fn main() {
             if let Some(input_path) = input_file {
                 let contents = fs::read_to_string(input_path).expect("Failed to read input file");
 
-                let mut playlist_items = Vec::<SimplePlaylistItem>::new();
-
-                let html = Html::parse_fragment(&contents);
-
-                let item_selector = Selector::parse("#content").unwrap();
-                let video_title = Selector::parse("#video-title").unwrap();
-                let channel_title = Selector::parse("#text").unwrap();
-                let video_link = Selector::parse("#content > a").unwrap();
-
-                let items = html.select(&item_selector);
-                for item in items {
-                    let mut title = item.select(&video_title);
-                    let mut channel = item.select(&channel_title);
-                    let mut video_link = item.select(&video_link);
-
-                    let item_title = if let Some(a) = title.next() {
-                        let item_title = a.text().collect::<String>().trim().to_string();
-                        println!("{:?}", item_title);
-                        item_title
-                    } else {
-                        println!("No title?");
-                        String::new()
-                    };
-
-                    let item_channel = if let Some(a) = channel.next() {
-                        let item_channel = a.text().collect::<String>().trim().to_string();
-                        println!("{:?}", item_channel);
-                        item_channel
-                    } else {
-                        println!("No channel title?");
-                        String::new()
-                    };
-
-                    let item_link = if let Some(a) = video_link.next() {
-                        let item_link = a.value().attr("href").unwrap_or("").to_string();
-                        println!("{:?}", item_link);
-                        let video_id = split_video_id(&item_link);
-                        println!("{:?}", video_id);
-                        (item_link, video_id)
-                    } else {
-                        println!("No video_link?");
-                        (String::new(), String::new())
-                    };
-
-                    println!("");
-
-                    let item = SimplePlaylistItem {
-                        title: item_title,
-                        channel_name: item_channel,
-                        id: item_link.1,
-                        link: item_link.0,
-                    };
-
-                    playlist_items.push(item);
-                }
-
-                let playlist_items = playlist_items.iter().filter(|x| !x.id.is_empty()).collect::<Vec<&SimplePlaylistItem>>();
+                let playlist_items = scrape_simple_playlist_items(&contents);
 
                 let path = if let Some(path) = output_file {
                     path
@@ -404,5 +1029,164 @@ fn main() {
                 eprintln!("Could not find input file: {:?}", input_file);
             };
         }
+        Subcommands::ScrapePlaylistToJson {
+            playlist_id,
+            output_file,
+        } => {
+            let playlist = scrape_playlist(&playlist_id);
+            let item_count = playlist.items.len();
+            let output_playlists = vec![playlist];
+
+            let path = if let Some(path) = output_file {
+                path
+            } else {
+                Path::new("youtube-output-scraped.json").to_path_buf()
+            };
+
+            let json_text = serde_json::to_string(&output_playlists);
+            if let Ok(text) = json_text {
+                fs::write(path, &text).expect("Unable to write file");
+                println!("Wrote {} items", item_count);
+            }
+        }
+        Subcommands::SavePlaylistsToRss {
+            input_file,
+            output_file,
+        } => {
+            let output_playlists = if let Some(input_path) = input_file {
+                let contents = fs::read_to_string(&input_path).expect("Failed to read input file");
+
+                if let Ok(playlists) = serde_json::from_str::<Vec<Playlist>>(&contents) {
+                    playlists
+                } else if let Ok(items) = serde_json::from_str::<Vec<SimplePlaylistItem>>(&contents) {
+                    vec![simple_items_to_playlist("watch-later", items)]
+                } else {
+                    panic!(
+                        "Could not parse {:?} as a saved playlist or watch later export",
+                        input_path
+                    );
+                }
+            } else {
+                let client = build_client(application_secret_file);
+                fetch_all_playlists(&client)
+            };
+
+            let path = if let Some(path) = output_file {
+                path
+            } else {
+                Path::new("youtube-output.rss").to_path_buf()
+            };
+
+            let rss_text = playlists_to_rss(&output_playlists);
+            fs::write(path, &rss_text).expect("Unable to write file");
+            println!("Wrote {} playlists", output_playlists.len());
+        }
+        Subcommands::WatchPlaylist {
+            playlist_id,
+            poll_interval_secs,
+            state_file,
+        } => {
+            let client = build_client(application_secret_file);
+            loop {
+                let items = parse_playlist_items(&client, &playlist_id);
+                let current_items = items.iter().map(parse_playlist_item).collect::<Vec<PlaylistItem>>();
+
+                let previous_items = load_watch_state(&state_file);
+                print_watch_diff(&previous_items, &current_items);
+
+                save_watch_state(&state_file, &current_items);
+
+                thread::sleep(Duration::from_secs(poll_interval_secs));
+            }
+        }
+        Subcommands::DownloadPlaylist {
+            input_file,
+            output_dir,
+            audio_only,
+        } => {
+            fs::create_dir_all(&output_dir).expect("Unable to create output directory");
+            let contents = fs::read_to_string(&input_file).expect("Failed to read input file");
+
+            if let Ok(mut playlists) = serde_json::from_str::<Vec<Playlist>>(&contents) {
+                for playlist in &mut playlists {
+                    for item in &mut playlist.items {
+                        download_playlist_item(item, &output_dir, audio_only);
+                    }
+                }
+
+                let json_text = serde_json::to_string(&playlists);
+                if let Ok(text) = json_text {
+                    fs::write(&input_file, &text).expect("Unable to write file");
+                }
+                println!("Downloaded items from {} playlists", playlists.len());
+            } else if let Ok(mut items) = serde_json::from_str::<Vec<SimplePlaylistItem>>(&contents) {
+                for (i, item) in items.iter_mut().enumerate() {
+                    download_simple_playlist_item(item, i as u32, &output_dir, audio_only);
+                }
+
+                let json_text = serde_json::to_string(&items);
+                if let Ok(text) = json_text {
+                    fs::write(&input_file, &text).expect("Unable to write file");
+                }
+                println!("Downloaded {} items", items.len());
+            } else {
+                eprintln!(
+                    "Could not parse {:?} as a saved playlist or watch later export",
+                    input_file
+                );
+            }
+        }
+        Subcommands::RestorePlaylistsFromJson {
+            input_file,
+            dry_run,
+        } => {
+            let contents = fs::read_to_string(&input_file).expect("Failed to read input file");
+            let playlists = serde_json::from_str::<Vec<Playlist>>(&contents)
+                .expect("Failed to parse input file as saved playlists");
+
+            if dry_run {
+                for playlist in &playlists {
+                    println!(
+                        "[dry-run] would create playlist {:?} (privacy: {})",
+                        playlist.title, playlist.status
+                    );
+
+                    let mut items = playlist.items.iter().collect::<Vec<&PlaylistItem>>();
+                    items.sort_by_key(|item| item.position_in_playlist);
+
+                    for item in items {
+                        println!(
+                            "[dry-run]   would add video {} at position {}",
+                            split_video_id(&item.link),
+                            item.position_in_playlist
+                        );
+                    }
+                }
+                return;
+            }
+
+            let client = build_client(application_secret_file);
+
+            let mut restored_playlists = 0;
+            let mut failed_items = 0;
+
+            for playlist in &playlists {
+                match restore_playlist(&client, playlist) {
+                    Ok(item_failures) => {
+                        restored_playlists += 1;
+                        failed_items += item_failures;
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to create playlist {:?}: {}", playlist.title, e);
+                        failed_items += playlist.items.len();
+                    }
+                }
+            }
+
+            println!(
+                "Restored {} playlists, {} item failures",
+                restored_playlists, failed_items
+            );
+        }
     }
 }